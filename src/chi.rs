@@ -1,16 +1,121 @@
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate};
 
-#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Gender {
     Male,
     Female,
 }
 
+/// Reasons a string can fail to be a valid CHI number.
+///
+/// Returned by [`try_from`](Chi::try_from) and the fallible accessors so callers validating
+/// user-supplied data in bulk can report precisely why a value was rejected rather than simply
+/// crashing.
+#[derive(PartialEq, Debug)]
+pub enum ChiError {
+    /// The value was not exactly 10 characters long.
+    WrongLength,
+    /// The value contained a character that was not an ASCII digit.
+    NonDigit,
+    /// The modulus 11 check digit did not match the first nine digits.
+    BadCheckDigit,
+    /// The leading `DDMMYY` digits did not form a valid calendar date.
+    ImpossibleDate,
+}
+
+impl std::fmt::Display for ChiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ChiError::WrongLength => "CHI should be 10 characters long",
+            ChiError::NonDigit => "CHI must contain only digits",
+            ChiError::BadCheckDigit => "CHI last digit must pass modulus 11 test",
+            ChiError::ImpossibleDate => "CHI date of birth digits must form a valid date",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for ChiError {}
+
+/// Decode the ten characters of a candidate CHI into their digit values, rejecting anything that
+/// is the wrong length or contains non-digits.
+fn decode_digits(string: &str) -> Result<[u32; 10], ChiError> {
+    if string.len() != 10 {
+        return Err(ChiError::WrongLength);
+    }
+    let mut digits = [0u32; 10];
+    for (slot, c) in digits.iter_mut().zip(string.chars()) {
+        *slot = c.to_digit(10).ok_or(ChiError::NonDigit)?;
+    }
+    Ok(digits)
+}
+
+/// Check the modulus 11 check digit of an already-decoded CHI.
+fn check_digit(digits: &[u32; 10]) -> Result<(), ChiError> {
+    let sum: u32 = (2..=10).rev().zip(digits[0..9].iter()).map(|(n, d)| n * d).sum();
+    let modulus = 11 - (sum % 11);
+    let corrected = if modulus == 11 { 0 } else { modulus };
+    if corrected == digits[9] {
+        Ok(())
+    } else {
+        Err(ChiError::BadCheckDigit)
+    }
+}
+
+/// Completed years between a date of birth and a reference date.
+///
+/// Subtracts a year when the reference date falls before the birthday in its own year. A 29
+/// February birthday is treated as 28 February in non-leap reference years.
+fn completed_years(dob: NaiveDate, on: NaiveDate) -> u32 {
+    let birthday = if dob.month() == 2
+        && dob.day() == 29
+        && NaiveDate::from_ymd_opt(on.year(), 2, 29).is_none()
+    {
+        (2, 28)
+    } else {
+        (dob.month(), dob.day())
+    };
+    let mut years = on.year() - dob.year();
+    if (on.month(), on.day()) < birthday {
+        years -= 1;
+    }
+    years.max(0) as u32
+}
+
+/// Return `true` if `string` is a syntactically valid CHI number: ten digits, a valid `DDMMYY`
+/// date and a correct modulus 11 check digit.
+///
+/// This mirrors the `is_valid`/`validate` split in the idcard crate and never panics, so it is
+/// suitable for filtering user-supplied data in bulk.
+pub fn validate(string: &str) -> bool {
+    let Ok(digits) = decode_digits(string) else {
+        return false;
+    };
+    if check_digit(&digits).is_err() {
+        return false;
+    }
+    let day = digits[0] * 10 + digits[1];
+    let month = digits[2] * 10 + digits[3];
+    let year_end = (digits[4] * 10 + digits[5]) as i32;
+    // The date is accepted if it exists in either the 1900s or the 2000s, so a 29 February that
+    // is only valid in some centuries passes; the century-specific check happens when the date is
+    // actually decoded.
+    NaiveDate::from_ymd_opt(1900 + year_end, month, day).is_some()
+        || NaiveDate::from_ymd_opt(2000 + year_end, month, day).is_some()
+}
+
 pub trait Chi {
     /// Construct a Chi from a string, validating the modulus 11 check digit at the end of the
     /// value.
     fn from(string: &'static str) -> Self;
 
+    /// Fallibly construct a Chi, returning a [`ChiError`] describing the first problem found
+    /// instead of panicking. Preferred over [`from`](Chi::from) when the input is not trusted.
+    fn try_from(string: &'static str) -> Result<Self, ChiError>
+    where
+        Self: Sized;
+
     /// Extract date of birth from Community Health Index (CHI) number
     ///
     /// The Community Health Index (CHI) is a population register used in Scotland
@@ -21,7 +126,28 @@ pub trait Chi {
     /// considered 2018, rather than 1918.
     fn date_of_birth(&self, cutoff_2000: u32) -> NaiveDate;
 
+    /// Decode the date of birth, inferring the century from `today` on the assumption that nobody
+    /// in the index was born in the future: the 2000s reading is used unless it lands after
+    /// `today`, in which case the 1900s reading is taken. This is the recommended alternative to
+    /// the explicit-cutoff [`date_of_birth`](Chi::date_of_birth).
+    fn date_of_birth_auto(&self, today: NaiveDate) -> NaiveDate;
+
+    /// Like [`date_of_birth`](Chi::date_of_birth) but returns [`ChiError::ImpossibleDate`] rather
+    /// than panicking when the digits do not form a valid `NaiveDate`.
+    fn try_date_of_birth(&self, cutoff_2000: u32) -> Result<NaiveDate, ChiError>;
+
     fn gender(&self) -> Gender;
+
+    /// Like [`gender`](Chi::gender) but returns a [`ChiError`] rather than panicking when the
+    /// value is not ten ASCII digits. The check digit and the date digits are not checked.
+    fn try_gender(&self) -> Result<Gender, ChiError>;
+
+    /// Completed years between the CHI-encoded date of birth and `on`, treating a 29 February
+    /// birthday as 28 February in non-leap years.
+    fn age_on(&self, on: NaiveDate, cutoff_2000: u32) -> u32;
+
+    /// Completed years as of today, using [`chrono::Local`].
+    fn age(&self, cutoff_2000: u32) -> u32;
 }
 
 impl Chi for &'static str {
@@ -43,6 +169,20 @@ impl Chi for &'static str {
         string
     }
 
+    fn try_from(string: &'static str) -> Result<Self, ChiError> {
+        let digits = decode_digits(string)?;
+        let day = digits[0] * 10 + digits[1];
+        let month = digits[2] * 10 + digits[3];
+        let year_end = (digits[4] * 10 + digits[5]) as i32;
+        if NaiveDate::from_ymd_opt(1900 + year_end, month, day).is_none()
+            && NaiveDate::from_ymd_opt(2000 + year_end, month, day).is_none()
+        {
+            return Err(ChiError::ImpossibleDate);
+        }
+        check_digit(&digits)?;
+        Ok(string)
+    }
+
     fn date_of_birth(&self, cutoff_2000: u32) -> NaiveDate {
         let day = self[0..2].parse().unwrap();
         let month = self[2..4].parse().unwrap();
@@ -56,19 +196,186 @@ impl Chi for &'static str {
         // NaiveDate::parse_from_str(&self[0..6], "%d%m%y").unwrap()
     }
 
+    fn date_of_birth_auto(&self, today: NaiveDate) -> NaiveDate {
+        let day = self[0..2].parse().unwrap();
+        let month = self[2..4].parse().unwrap();
+        let year_end: i32 = self[4..6].parse().unwrap();
+        let candidate = NaiveDate::from_ymd_opt(2000 + year_end, month, day).unwrap();
+        if candidate > today {
+            NaiveDate::from_ymd_opt(1900 + year_end, month, day).unwrap()
+        } else {
+            candidate
+        }
+    }
+
+    fn try_date_of_birth(&self, cutoff_2000: u32) -> Result<NaiveDate, ChiError> {
+        let digits = decode_digits(self)?;
+        let day = digits[0] * 10 + digits[1];
+        let month = digits[2] * 10 + digits[3];
+        let year_end = (digits[4] * 10 + digits[5]) as i32;
+        let year = if year_end > cutoff_2000 as i32 {
+            1900 + year_end
+        } else {
+            2000 + year_end
+        };
+        NaiveDate::from_ymd_opt(year, month, day).ok_or(ChiError::ImpossibleDate)
+    }
+
     fn gender(&self) -> Gender {
         match (self.chars().nth(8).unwrap() as u32 - '0' as u32) % 2 {
             0 => Gender::Female,
             _ => Gender::Male,
         }
     }
+
+    fn try_gender(&self) -> Result<Gender, ChiError> {
+        let digits = decode_digits(self)?;
+        match digits[8] % 2 {
+            0 => Ok(Gender::Female),
+            _ => Ok(Gender::Male),
+        }
+    }
+
+    fn age_on(&self, on: NaiveDate, cutoff_2000: u32) -> u32 {
+        completed_years(self.date_of_birth(cutoff_2000), on)
+    }
+
+    fn age(&self, cutoff_2000: u32) -> u32 {
+        self.age_on(chrono::Local::now().date_naive(), cutoff_2000)
+    }
+}
+
+/// Build syntactically valid CHI numbers, for test fixtures and anonymised data.
+///
+/// Analogous to the `fake` module in the idcard crate: the numbers produced here pass
+/// [`validate`] and round-trip through [`date_of_birth`](Chi::date_of_birth) and
+/// [`gender`](Chi::gender), so downstream crates can seed databases with realistic identifiers.
+pub mod fake {
+    use super::Gender;
+    use chrono::NaiveDate;
+
+    /// Build a valid 10-character CHI for the given date of birth, gender and serial.
+    ///
+    /// The first six digits are `DDMMYY` from `dob`; digits seven to nine hold `serial`, whose
+    /// last digit encodes gender (odd = male, even = female); the tenth is the modulus 11 check
+    /// digit. Because a serial whose check digit would be 10 cannot be represented in one
+    /// character — and because the ninth digit's parity is fixed by `gender` — the serial is
+    /// incremented (wrapping within `000..=999`) and retried until a valid CHI results.
+    pub fn generate(dob: NaiveDate, gender: Gender, serial: u16) -> String {
+        let date = dob.format("%d%m%y").to_string();
+        let mut serial = serial % 1000;
+        loop {
+            let parity_ok = match gender {
+                Gender::Male => serial % 2 == 1,
+                Gender::Female => serial % 2 == 0,
+            };
+            if !parity_ok {
+                serial = (serial + 1) % 1000;
+                continue;
+            }
+            let core = format!("{date}{serial:03}");
+            let digits: Vec<u32> = core.chars().map(|c| c.to_digit(10).unwrap()).collect();
+            let sum: u32 = (2..=10).rev().zip(digits.iter()).map(|(n, d)| n * d).sum();
+            let modulus = 11 - (sum % 11);
+            let check = if modulus == 11 { 0 } else { modulus };
+            if check == 10 {
+                serial = (serial + 1) % 1000;
+                continue;
+            }
+            return format!("{core}{check}");
+        }
+    }
+
+    /// Build a valid CHI for a randomly chosen date of birth, gender and serial.
+    pub fn generate_random() -> String {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let year = rng.gen_range(1900..=2023);
+        let month = rng.gen_range(1..=12);
+        // 1..=28 is a valid day in every month, so the date is always representable.
+        let day = rng.gen_range(1..=28);
+        let dob = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let gender = if rng.gen() { Gender::Male } else { Gender::Female };
+        generate(dob, gender, rng.gen_range(0..1000))
+    }
+}
+
+/// An owned, pre-validated CHI number.
+///
+/// The trait-on-`&'static str` design above requires every CHI to be a string literal, which is
+/// impractical for data read at runtime from files or the network. [`Chi`](owned::Chi) parses and
+/// validates once at construction and caches the decoded date of birth and gender, so it can flow
+/// through JSON and serde pipelines without re-validating on every field access.
+pub mod owned {
+    use super::{check_digit, decode_digits, ChiError, Gender};
+    use chrono::NaiveDate;
+
+    /// An owned CHI whose date of birth and gender have been decoded and cached.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[derive(PartialEq, Eq, Clone, Debug)]
+    pub struct Chi {
+        chi: String,
+        date_of_birth: NaiveDate,
+        gender: Gender,
+    }
+
+    impl Chi {
+        /// Parse and validate a CHI, caching its decoded date of birth (using `cutoff_2000` to
+        /// pick the century, see [`date_of_birth`](super::Chi::date_of_birth)) and gender.
+        pub fn new(chi: &str, cutoff_2000: u32) -> Result<Self, ChiError> {
+            let digits = decode_digits(chi)?;
+            let day = digits[0] * 10 + digits[1];
+            let month = digits[2] * 10 + digits[3];
+            let year_end = (digits[4] * 10 + digits[5]) as i32;
+            let year = if year_end > cutoff_2000 as i32 {
+                1900 + year_end
+            } else {
+                2000 + year_end
+            };
+            let date_of_birth =
+                NaiveDate::from_ymd_opt(year, month, day).ok_or(ChiError::ImpossibleDate)?;
+            check_digit(&digits)?;
+            let gender = if digits[8] % 2 == 0 {
+                Gender::Female
+            } else {
+                Gender::Male
+            };
+            Ok(Chi {
+                chi: chi.to_string(),
+                date_of_birth,
+                gender,
+            })
+        }
+
+        /// The underlying ten-character CHI string.
+        pub fn as_str(&self) -> &str {
+            &self.chi
+        }
+
+        /// The cached date of birth.
+        pub fn date_of_birth(&self) -> NaiveDate {
+            self.date_of_birth
+        }
+
+        /// The cached gender.
+        pub fn gender(&self) -> Gender {
+            self.gender
+        }
+
+        /// Serialise to `{ "chi": ..., "date_of_birth": ..., "gender": ... }`, like idcard's
+        /// `to_json_string`.
+        #[cfg(feature = "serde")]
+        pub fn to_json(&self) -> String {
+            serde_json::to_string(self).expect("owned::Chi is always serialisable")
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use chrono::NaiveDate;
 
-    use crate::chi::{Chi, Gender};
+    use crate::chi::{validate, Chi, ChiError, Gender};
 
     #[test]
     fn valid_date_of_birth() {
@@ -114,4 +421,100 @@ mod tests {
     fn wrong_length() {
         let _x: &'static str = Chi::from("100970123");
     }
+
+    #[test]
+    fn validate_accepts_and_rejects() {
+        assert!(validate("1811431232"));
+        assert!(!validate("1811431233")); // bad check digit
+        assert!(!validate("100970123")); // too short
+        assert!(!validate("18114312ab")); // non-digit
+        assert!(!validate("9911431230")); // impossible date
+    }
+
+    #[test]
+    fn try_from_reports_the_reason() {
+        assert!(Chi::try_from("1811431232").is_ok());
+        assert_eq!(Chi::try_from("100970123"), Err(ChiError::WrongLength));
+        assert_eq!(Chi::try_from("18114312ab"), Err(ChiError::NonDigit));
+        assert_eq!(Chi::try_from("1811431233"), Err(ChiError::BadCheckDigit));
+        assert_eq!(Chi::try_from("9911431230"), Err(ChiError::ImpossibleDate));
+    }
+
+    #[test]
+    fn fallible_accessors_do_not_panic() {
+        let x: &'static str = Chi::from("1811431232");
+        assert_eq!(
+            x.try_date_of_birth(23),
+            Ok(NaiveDate::from_ymd_opt(1943, 11, 18).unwrap())
+        );
+        assert_eq!(x.try_gender(), Ok(Gender::Male));
+        assert_eq!("9911431230".try_date_of_birth(23), Err(ChiError::ImpossibleDate));
+    }
+
+    #[test]
+    fn generated_chi_is_valid_and_round_trips() {
+        let dob = NaiveDate::from_ymd_opt(1943, 11, 18).unwrap();
+        let chi = crate::chi::fake::generate(dob, Gender::Male, 123);
+        assert!(validate(&chi));
+        assert!(chi.starts_with("181143"));
+    }
+
+    #[test]
+    fn generated_chi_honours_requested_gender() {
+        let dob = NaiveDate::from_ymd_opt(2001, 1, 1).unwrap();
+        // The ninth digit's parity carries the gender: odd for male, even for female.
+        let female = crate::chi::fake::generate(dob, Gender::Female, 200);
+        let male = crate::chi::fake::generate(dob, Gender::Male, 200);
+        let ninth = |s: &str| s.chars().nth(8).unwrap().to_digit(10).unwrap();
+        assert_eq!(ninth(&female) % 2, 0);
+        assert_eq!(ninth(&male) % 2, 1);
+        assert!(validate(&female) && validate(&male));
+    }
+
+    #[test]
+    fn generate_random_is_valid() {
+        assert!(validate(&crate::chi::fake::generate_random()));
+    }
+
+    #[test]
+    fn date_of_birth_auto_infers_century() {
+        let today = NaiveDate::from_ymd_opt(2025, 7, 25).unwrap();
+        // "23" reads as 2023, which is in the past.
+        let y: &'static str = Chi::from("1304236366");
+        assert_eq!(y.date_of_birth_auto(today), NaiveDate::from_ymd_opt(2023, 4, 13).unwrap());
+        // "43" as 2043 would be in the future, so it falls back to 1943.
+        let x: &'static str = Chi::from("1811431232");
+        assert_eq!(x.date_of_birth_auto(today), NaiveDate::from_ymd_opt(1943, 11, 18).unwrap());
+    }
+
+    #[test]
+    fn age_on_counts_completed_years() {
+        let x: &'static str = Chi::from("1811431232"); // born 18 Nov 1943
+        assert_eq!(x.age_on(NaiveDate::from_ymd_opt(2000, 11, 18).unwrap(), 23), 57);
+        assert_eq!(x.age_on(NaiveDate::from_ymd_opt(2000, 11, 17).unwrap(), 23), 56);
+    }
+
+    #[test]
+    fn age_on_handles_29_february_birthday() {
+        // A 29 Feb 2020 birthday; on a non-leap year the birthday lands on 28 Feb.
+        let chi = crate::chi::fake::generate(
+            NaiveDate::from_ymd_opt(2020, 2, 29).unwrap(),
+            Gender::Female,
+            0,
+        );
+        let dob = NaiveDate::from_ymd_opt(2020, 2, 29).unwrap();
+        assert_eq!(super::completed_years(dob, NaiveDate::from_ymd_opt(2023, 2, 28).unwrap()), 3);
+        assert_eq!(super::completed_years(dob, NaiveDate::from_ymd_opt(2023, 2, 27).unwrap()), 2);
+        assert!(validate(&chi));
+    }
+
+    #[test]
+    fn owned_chi_parses_once() {
+        use crate::chi::owned;
+        let chi = owned::Chi::new("1811431232", 23).unwrap();
+        assert_eq!(chi.as_str(), "1811431232");
+        assert_eq!(chi.date_of_birth(), NaiveDate::from_ymd_opt(1943, 11, 18).unwrap());
+        assert_eq!(chi.gender(), Gender::Male);
+        assert_eq!(owned::Chi::new("100970123", 23), Err(ChiError::WrongLength));
+    }
 }